@@ -1,10 +1,8 @@
 use std::io::Write;
 use std::fs::OpenOptions;
 
-use rusoto_core::Region;
-use rusoto_s3::{Object, ListObjectsV2Request, S3, S3Client};
-
-use crate::{Result, AWS_BUCKET_NAME};
+use crate::s3::{Object, S3Client};
+use crate::{Result, AWS_BUCKET_NAME, AWS_REGION, AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY};
 use crate::backblaze::{BlazeCache, get_or_update_blaze_cache};
 
 
@@ -18,13 +16,7 @@ pub struct AWSTransferCache {
 
 impl AWSTransferCache {
 	async fn req_more_objects(&mut self) -> Result<()> {
-		let objs = self.aws.list_objects_v2(ListObjectsV2Request {
-			bucket: String::from(AWS_BUCKET_NAME),
-			continuation_token: self.next_continuation_token.clone(),
-			// max_keys: Some(5),
-
-			.. Default::default()
-		}).await;
+		let objs = self.aws.list_objects_v2(self.next_continuation_token.clone()).await;
 
 		let objs = match objs {
 			Ok(v) => v,
@@ -35,7 +27,7 @@ impl AWSTransferCache {
 		};
 
 		self.next_continuation_token = objs.next_continuation_token;
-		self.cache = objs.contents.unwrap_or_default();
+		self.cache = objs.contents;
 		self.cache.reverse();
 
 		Ok(())
@@ -55,7 +47,12 @@ impl AWSTransferCache {
 impl Default for AWSTransferCache {
 	fn default() -> Self {
 		AWSTransferCache {
-			aws: S3Client::new(Region::UsEast1),
+			aws: S3Client::new(
+				AWS_BUCKET_NAME.to_string(),
+				AWS_REGION.to_string(),
+				AWS_ACCESS_KEY_ID.to_string(),
+				AWS_SECRET_ACCESS_KEY.to_string()
+			),
 			next_continuation_token: None,
 			cache: Vec::new()
 		}
@@ -94,8 +91,8 @@ pub async fn run_aws_cache() -> Result<()> {
 			println!("POS: {}", pos);
 		}
 
-		if object.size.unwrap_or_default() > 0 && object.key.is_some() {
-			let mut bytes = object.key.unwrap().into_bytes();
+		if object.size > 0 {
+			let mut bytes = object.key.into_bytes();
 			bytes.push(b'\n');
 
 			file_cache.write_all(&bytes)?;
@@ -11,7 +11,8 @@ use std::{
 use crypto::sha1::Sha1;
 use crypto::digest::Digest;
 use base64::encode as b64encode;
-use reqwest::Client;
+use futures::stream;
+use reqwest::{Body, Client};
 
 use crate::Result;
 
@@ -23,6 +24,34 @@ const API_URL_V2: &str = "https://api.backblazeb2.com/b2api/v2";
 // const API_URL_V4: &str = "https://api.backblazeb2.com/b2api/v4";
 // const API_URL_V5: &str = "https://api.backblazeb2.com/b2api/v5";
 
+/// A SHA1 hex digest is always 40 characters; `hex_digits_at_end` appends exactly that many
+/// bytes to the body, so `Content-Length` has to account for them up front.
+const TRAILING_SHA1_HEX_LEN: usize = 40;
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Streams `data` chunk-by-chunk, hashing as it goes, and appends the SHA1 hex digest as a final
+/// chunk once exhausted - B2's `hex_digits_at_end` trailer format.
+fn body_with_trailing_sha1(data: Vec<u8>) -> Body {
+	Body::wrap_stream(stream::unfold((data, 0, Sha1::new(), false), |(data, pos, mut sha, finished)| async move {
+		if finished {
+			return None;
+		}
+
+		if pos < data.len() {
+			let end = (pos + STREAM_CHUNK_SIZE).min(data.len());
+			let chunk = data[pos..end].to_vec();
+
+			sha.input(&chunk);
+
+			Some((Ok::<_, std::io::Error>(chunk), (data, end, sha, false)))
+		} else {
+			let digest = sha.result_str().into_bytes();
+
+			Some((Ok(digest), (data, pos, sha, true)))
+		}
+	}))
+}
+
 
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,6 +61,14 @@ pub struct BlazeError {
 	pub message: String
 }
 
+impl std::fmt::Display for BlazeError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} ({}): {}", self.code, self.status, self.message)
+	}
+}
+
+impl std::error::Error for BlazeError {}
+
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlazeCache {
@@ -205,23 +242,62 @@ impl B2Authorization {
 		}
 	}
 
-	pub async fn upload_file(&self, upload: &UploadUrlResponse, file_name: String, image: Vec<u8>) -> Result<serde_json::Value> {
+	pub async fn start_large_file(&self, bucket_id: &str, file_name: &str, src_etag: &str) -> Result<StartLargeFileResponse> {
+		let client = Client::new();
+
+		let body = serde_json::json!({
+			"bucketId": bucket_id,
+			"fileName": encode_file_name(file_name.to_string()),
+			"contentType": "b2/x-auto",
+			"fileInfo": { "src_etag": src_etag }
+		});
+
+		let resp = client.post(format!("{}/b2api/v2/b2_start_large_file", self.api_url).as_str())
+			.header("Authorization", self.authorization_token.as_str())
+			.body(serde_json::to_string(&body)?)
+			.send()
+			.await?;
+
+		if resp.status().is_success() {
+			Ok(resp.json().await?)
+		} else {
+			Err(crate::error::Error::Blaze(resp.json().await?))
+		}
+	}
+
+	pub async fn get_upload_part_url(&self, file_id: &str) -> Result<UploadPartUrlResponse> {
+		let client = Client::new();
+
+		let body = serde_json::json!({
+			"fileId": file_id
+		});
+
+		let resp = client.post(format!("{}/b2api/v2/b2_get_upload_part_url", self.api_url).as_str())
+			.header("Authorization", self.authorization_token.as_str())
+			.body(serde_json::to_string(&body)?)
+			.send()
+			.await?;
+
+		if resp.status().is_success() {
+			Ok(resp.json().await?)
+		} else {
+			Err(crate::error::Error::Blaze(resp.json().await?))
+		}
+	}
+
+	pub async fn upload_part(&self, upload: &UploadPartUrlResponse, part_number: u16, data: Vec<u8>) -> Result<UploadPartResponse> {
 		let client = Client::new();
 
 		let mut sha = Sha1::new();
-		sha.input(image.as_ref());
+		sha.input(data.as_ref());
 		let sha = sha.result_str();
 
-		// println!("Size: {}", image.len());
-		// println!("Sha1: {}", sha);
-
 		let resp = client.post(upload.upload_url.as_str())
 			.header("Authorization", upload.authorization_token.as_str())
-			.header("Content-Type", "b2/x-auto")
-			.header("Content-Length", image.len())
-			.header("X-Bz-File-Name", encode_file_name(file_name).as_str())
+			.header("Content-Length", data.len())
+			.header("X-Bz-Part-Number", part_number)
 			.header("X-Bz-Content-Sha1", sha.as_str())
-			.body(image)
+			.body(data)
 			.send()
 			.await?;
 
@@ -232,6 +308,90 @@ impl B2Authorization {
 		}
 	}
 
+	pub async fn finish_large_file(&self, file_id: &str, part_sha1_array: Vec<String>) -> Result<serde_json::Value> {
+		let client = Client::new();
+
+		let body = serde_json::json!({
+			"fileId": file_id,
+			"partSha1Array": part_sha1_array
+		});
+
+		let resp = client.post(format!("{}/b2api/v2/b2_finish_large_file", self.api_url).as_str())
+			.header("Authorization", self.authorization_token.as_str())
+			.body(serde_json::to_string(&body)?)
+			.send()
+			.await?;
+
+		if resp.status().is_success() {
+			Ok(resp.json().await?)
+		} else {
+			Err(crate::error::Error::Blaze(resp.json().await?))
+		}
+	}
+
+	pub async fn cancel_large_file(&self, file_id: &str) -> Result<serde_json::Value> {
+		let client = Client::new();
+
+		let body = serde_json::json!({
+			"fileId": file_id
+		});
+
+		let resp = client.post(format!("{}/b2api/v2/b2_cancel_large_file", self.api_url).as_str())
+			.header("Authorization", self.authorization_token.as_str())
+			.body(serde_json::to_string(&body)?)
+			.send()
+			.await?;
+
+		if resp.status().is_success() {
+			Ok(resp.json().await?)
+		} else {
+			Err(crate::error::Error::Blaze(resp.json().await?))
+		}
+	}
+
+	/// When `stream_sha1` is set, the SHA1 is computed as the body streams out (B2's
+	/// `hex_digits_at_end`) instead of in a separate pass over `image` first.
+	pub async fn upload_file(&self, upload: &UploadUrlResponse, file_name: String, image: Vec<u8>, stream_sha1: bool, src_etag: &str) -> Result<serde_json::Value> {
+		let client = Client::new();
+
+		let request = client.post(upload.upload_url.as_str())
+			.header("Authorization", upload.authorization_token.as_str())
+			.header("Content-Type", "b2/x-auto")
+			.header("X-Bz-File-Name", encode_file_name(file_name).as_str());
+
+		let request = if !src_etag.is_empty() {
+			request.header("X-Bz-Info-src_etag", src_etag)
+		} else {
+			request
+		};
+
+		let request = if stream_sha1 {
+			let content_length = image.len() + TRAILING_SHA1_HEX_LEN;
+
+			request
+				.header("Content-Length", content_length)
+				.header("X-Bz-Content-Sha1", "hex_digits_at_end")
+				.body(body_with_trailing_sha1(image))
+		} else {
+			let mut sha = Sha1::new();
+			sha.input(image.as_ref());
+			let sha = sha.result_str();
+
+			request
+				.header("Content-Length", image.len())
+				.header("X-Bz-Content-Sha1", sha.as_str())
+				.body(image)
+		};
+
+		let resp = request.send().await?;
+
+		if resp.status().is_success() {
+			Ok(resp.json().await?)
+		} else {
+			Err(crate::error::Error::Blaze(resp.json().await?))
+		}
+	}
+
 	// ^ Returns.
 	// Object({
 	// 	"accountId": String(
@@ -266,6 +426,29 @@ pub struct UploadUrlResponse {
 	pub upload_url: String
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartLargeFileResponse {
+	pub file_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadPartUrlResponse {
+	pub file_id: String,
+	pub upload_url: String,
+	pub authorization_token: String
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadPartResponse {
+	pub file_id: String,
+	pub part_number: u16,
+	pub content_length: i64,
+	pub content_sha1: String
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ListFileNamesResponse {
@@ -279,14 +462,15 @@ pub struct FileName {
 	account_id: String,
 	action: String,
 	bucket_id: String,
-	content_length: i64,
-	content_sha1: String,
+	pub content_length: i64,
+	pub content_sha1: String,
 	content_md5: String,
 	content_type: String,
 	file_id: String,
-	file_name: String,
+	pub file_name: String,
+	#[serde(default)]
+	pub file_info: std::collections::HashMap<String, String>,
 	upload_timestamp: i64
-	// fileInfo
 }
 
 
@@ -1,6 +1,6 @@
 use std::{
+	error::Error as StdError,
 	fmt,
-	sync::LockResult,
 	io::Error as IoError
 };
 
@@ -20,21 +20,34 @@ pub enum Error {
 	Io(IoError),
 	Request(HttpError),
 
-	PoisonError,
-	RusotoError
+	/// A `Mutex` lock was poisoned; carries the name of the operation that tried to take it.
+	Poison(String),
+	S3(String)
 }
 
 
 impl fmt::Display for Error {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		match self {
-			Error::Blaze(e) => write!(f, "Blaze Error: {:?}", e),
-			Error::Text(e) => write!(f, "Custom Error: {:?}", e),
-			Error::Json(e) => write!(f, "JSON Error: {:?}", e),
-			Error::Io(e) => write!(f, "IO Error: {:?}", e),
-			Error::Request(e) => write!(f, "Request Error: {:?}", e),
-			Error::PoisonError => write!(f, "Posion Error"),
-			Error::RusotoError => write!(f, "Rusoto Error")
+			Error::Blaze(e) => write!(f, "Blaze Error: {}", e),
+			Error::Text(e) => write!(f, "Custom Error: {}", e),
+			Error::Json(e) => write!(f, "JSON Error: {}", e),
+			Error::Io(e) => write!(f, "IO Error: {}", e),
+			Error::Request(e) => write!(f, "Request Error: {}", e),
+			Error::Poison(op) => write!(f, "Poison Error: lock taken in {} was poisoned", op),
+			Error::S3(e) => write!(f, "S3 Error: {}", e)
+		}
+	}
+}
+
+impl StdError for Error {
+	fn source(&self) -> Option<&(dyn StdError + 'static)> {
+		match self {
+			Error::Json(e) => Some(e),
+			Error::Io(e) => Some(e),
+			Error::Request(e) => Some(e),
+			Error::Blaze(e) => Some(e),
+			Error::Text(_) | Error::Poison(_) | Error::S3(_) => None
 		}
 	}
 }
@@ -74,10 +87,4 @@ impl From<&str> for Error {
 	fn from(error: &str) -> Self {
 		Self::Text(error.to_string())
 	}
-}
-
-impl<ZZZZ> From<LockResult<ZZZZ>> for Error {
-	fn from(_: LockResult<ZZZZ>) -> Self {
-		Self::PoisonError
-	}
 }
\ No newline at end of file
@@ -4,6 +4,7 @@
 
 use std::rc::Rc;
 use std::sync::Mutex;
+use std::time::Duration;
 
 use futures::StreamExt;
 use tokio::{
@@ -13,23 +14,28 @@ use tokio::{
 	}
 };
 
-use rusoto_core::Region;
-use rusoto_s3::{GetObjectRequest, S3, S3Client};
-
 mod backblaze;
 pub mod error;
+pub mod s3;
 
 pub use error::Result;
 
-use backblaze::{BlazeCache, UploadUrlResponse, get_or_update_blaze_cache};
+use backblaze::{BlazeCache, UploadUrlResponse, UploadPartUrlResponse, get_or_update_blaze_cache};
+use s3::S3Client;
 
 
 pub static AWS_BUCKET_NAME: &str = "";
-pub static AWS_REGION: Region = Region::UsEast1;
+pub static AWS_REGION: &str = "us-east-1";
+pub static AWS_ACCESS_KEY_ID: &str = "";
+pub static AWS_SECRET_ACCESS_KEY: &str = "";
 pub static BLAZE_BUCKET_ID: &str = "";
 pub static BLAZE_CRED_ID: &str = "";
 pub static BLAZE_CRED_KEY: &str = "";
 
+pub static LARGE_FILE_PART_SIZE: usize = 8 * 1024 * 1024;
+pub static LARGE_FILE_PART_CONCURRENCY: usize = 4;
+pub static MAX_RETRY_ATTEMPTS: u32 = 5;
+
 
 pub mod aws_cache;
 pub use aws_cache::run_aws_cache;
@@ -50,7 +56,7 @@ impl BlazeUploadUrlCache {
 	}
 
 	pub async fn take_upload_url(&mut self, blaze: &BlazeCache) -> Result<UploadUrlResponse> {
-		let mut lock = self.0.lock().map_err(|_| error::Error::PoisonError)?;
+		let mut lock = self.0.lock().map_err(|_| error::Error::Poison("BlazeUploadUrlCache::take_upload_url".to_string()))?;
 
 		if let Some(value) = lock.pop() {
 			Ok(value)
@@ -60,7 +66,7 @@ impl BlazeUploadUrlCache {
 	}
 
 	pub fn return_upload_url(&mut self, url: UploadUrlResponse) -> Result<()> {
-		self.0.lock().map_err(|_| error::Error::PoisonError)?.push(url);
+		self.0.lock().map_err(|_| error::Error::Poison("BlazeUploadUrlCache::return_upload_url".to_string()))?.push(url);
 		Ok(())
 	}
 
@@ -70,17 +76,52 @@ impl BlazeUploadUrlCache {
 }
 
 
+#[derive(Clone)]
+pub struct BlazePartUploadUrlCache(Rc<Mutex<Vec<UploadPartUrlResponse>>>);
+
+impl BlazePartUploadUrlCache {
+	pub async fn new(blaze: &BlazeCache, file_id: &str) -> Result<Self> {
+		let mut urls: Vec<UploadPartUrlResponse> = Vec::new();
+
+		for _ in 0..LARGE_FILE_PART_CONCURRENCY {
+			urls.push(Self::new_part_upload_url(blaze, file_id).await?);
+		}
+
+		Ok(Self(Rc::new(Mutex::new(urls))))
+	}
+
+	pub async fn take_upload_url(&mut self, blaze: &BlazeCache, file_id: &str) -> Result<UploadPartUrlResponse> {
+		let mut lock = self.0.lock().map_err(|_| error::Error::Poison("BlazePartUploadUrlCache::take_upload_url".to_string()))?;
+
+		if let Some(value) = lock.pop() {
+			Ok(value)
+		} else {
+			Ok(Self::new_part_upload_url(blaze, file_id).await?)
+		}
+	}
+
+	pub fn return_upload_url(&mut self, url: UploadPartUrlResponse) -> Result<()> {
+		self.0.lock().map_err(|_| error::Error::Poison("BlazePartUploadUrlCache::return_upload_url".to_string()))?.push(url);
+		Ok(())
+	}
+
+	pub async fn new_part_upload_url(blaze: &BlazeCache, file_id: &str) -> Result<UploadPartUrlResponse> {
+		blaze.auth.get_upload_part_url(file_id).await
+	}
+}
+
+
 #[derive(Clone)]
 pub struct FailedTransfers(Rc<Mutex<BufWriter<File>>>);
 
 impl FailedTransfers {
 	pub async fn new() -> Result<Self> {
-		let cache = OpenOptions::new().write(true).create(true).open(".failed_transfers").await?;
+		let cache = OpenOptions::new().write(true).create(true).truncate(true).open(".failed_transfers").await?;
 		Ok(Self(Rc::new(Mutex::new(BufWriter::new(cache)))))
 	}
 
 	pub async fn add_url_to_failed(&self, file_url: String) -> Result<()> {
-		let mut lock = self.0.lock().map_err(|_| error::Error::PoisonError)?;
+		let mut lock = self.0.lock().map_err(|_| error::Error::Poison("FailedTransfers::add_url_to_failed".to_string()))?;
 
 		let mut bytes = file_url.into_bytes();
 		bytes.push(b'\n');
@@ -106,52 +147,178 @@ impl TransferToBlaze {
 			file_path,
 			blaze,
 			url_cache,
-			aws: S3Client::new(AWS_REGION.clone())
+			aws: S3Client::new(
+				AWS_BUCKET_NAME.to_string(),
+				AWS_REGION.to_string(),
+				AWS_ACCESS_KEY_ID.to_string(),
+				AWS_SECRET_ACCESS_KEY.to_string()
+			)
 		})
 	}
 
 	pub async fn download_and_send_next(mut self) -> Result<()> {
-		// let list = self.blaze.auth.list_file_names(&self.file_path, BLAZE_BUCKET_ID).await?;
+		let head = self.aws.head_object(&self.file_path).await?;
 
-		// if !list.files.is_empty() {
-		// 	return Ok(());
-		// }
+		if self.already_uploaded(&head).await? {
+			println!("Skipping (already in B2): {:?}", self.file_path);
+			return Ok(());
+		}
 
 		println!("Upload: {:?}", self.file_path);
 
-		let obj = self.aws.get_object(GetObjectRequest {
-			bucket: AWS_BUCKET_NAME.to_string(),
-			key: self.file_path.clone(),
+		let mut obj = self.aws.get_object(&self.file_path).await?;
 
-			.. Default::default()
-		}).await.map_err(|_| error::Error::RusotoError)?;
+		let part_size = LARGE_FILE_PART_SIZE.max(self.blaze.auth.absolute_minimum_part_size);
+		let first_part = read_part(&mut obj.body, part_size).await?;
 
-		if let Some(content) = obj.body {
-			let mut async_read = content.into_async_read();
+		if first_part.len() < part_size {
+			self.upload_single_part(first_part, &head.etag).await?;
+		} else {
+			self.upload_streamed_large_file(obj.body, first_part, part_size, &head.etag).await?;
+		}
+
+		Ok(())
+	}
 
-			let mut image = Vec::new();
-			async_read.read_to_end(&mut image).await?;
+	/// Compares against B2's `src_etag` fileInfo (stamped onto the object at upload time) when
+	/// present; older objects uploaded before that fall back to a size-only comparison.
+	async fn already_uploaded(&self, head: &s3::HeadObjectOutput) -> Result<bool> {
+		let list = self.blaze.auth.list_file_names(&self.file_path, BLAZE_BUCKET_ID).await?;
+		let encoded_name = backblaze::encode_file_name(self.file_path.clone());
 
-			let mut upload_url = self.url_cache.take_upload_url(&self.blaze).await?;
+		let existing = match list.files.iter().find(|f| f.file_name == encoded_name) {
+			Some(file) => file,
+			None => return Ok(false)
+		};
+
+		Ok(match existing.file_info.get("src_etag") {
+			Some(stored_etag) => stored_etag == &head.etag,
+			None => existing.content_length == head.content_length
+		})
+	}
 
-			if let Err(error::Error::Blaze(e)) = self.blaze.auth.upload_file(&upload_url, self.file_path.clone(), image.clone()).await {
-				if matches!(e.status, 401 | 503) {
-					upload_url = BlazeUploadUrlCache::new_upload_url(&self.blaze).await?;
-					self.blaze.auth.upload_file(&upload_url, self.file_path, image).await?;
-				} else {
-					return Err(e.into());
+	async fn upload_single_part(&mut self, image: Vec<u8>, src_etag: &str) -> Result<()> {
+		let mut upload_url = self.url_cache.take_upload_url(&self.blaze).await?;
+
+		if let Err(error::Error::Blaze(e)) = self.blaze.auth.upload_file(&upload_url, self.file_path.clone(), image.clone(), true, src_etag).await {
+			if matches!(e.status, 401 | 503) {
+				upload_url = BlazeUploadUrlCache::new_upload_url(&self.blaze).await?;
+				self.blaze.auth.upload_file(&upload_url, self.file_path.clone(), image, true, src_etag).await?;
+			} else {
+				return Err(e.into());
+			}
+		}
+
+		self.url_cache.return_upload_url(upload_url)?;
+
+		Ok(())
+	}
+
+	/// Reads `LARGE_FILE_PART_CONCURRENCY` parts ahead of `async_read` and uploads each batch
+	/// concurrently, bounding memory use to a few parts instead of the whole object.
+	async fn upload_streamed_large_file(
+		&mut self,
+		mut async_read: impl tokio::io::AsyncRead + Unpin,
+		first_part: Vec<u8>,
+		part_size: usize,
+		src_etag: &str
+	) -> Result<()> {
+		let start = self.blaze.auth.start_large_file(BLAZE_BUCKET_ID, &self.file_path, src_etag).await?;
+		let file_id = start.file_id;
+
+		let part_urls = BlazePartUploadUrlCache::new(&self.blaze, &file_id).await?;
+
+		let mut part_shas = Vec::new();
+		let mut part_number: u16 = 1;
+		let mut next_part = Some(first_part);
+		let mut done = false;
+
+		while !done {
+			let mut batch = Vec::new();
+
+			while batch.len() < LARGE_FILE_PART_CONCURRENCY {
+				let chunk = match next_part.take() {
+					Some(chunk) => chunk,
+					None => read_part(&mut async_read, part_size).await?
+				};
+
+				if chunk.is_empty() {
+					done = true;
+					break;
+				}
+
+				let is_final_part = chunk.len() < part_size;
+				batch.push((part_number, chunk));
+				part_number += 1;
+
+				if is_final_part {
+					done = true;
+					break;
 				}
 			}
 
-			self.url_cache.return_upload_url(upload_url)?;
-		} else {
-			println!("No Body for {}", self.file_path);
+			if batch.is_empty() {
+				break;
+			}
+
+			let uploads = futures::stream::iter(
+				batch.into_iter().map(|(number, chunk)| {
+					let blaze = self.blaze.clone();
+					let file_id = file_id.clone();
+					let mut part_urls = part_urls.clone();
+
+					async move {
+						let upload_url = part_urls.take_upload_url(&blaze, &file_id).await?;
+						let part = blaze.auth.upload_part(&upload_url, number, chunk).await?;
+						part_urls.return_upload_url(upload_url)?;
+
+						Ok::<(u16, String), error::Error>((number, part.content_sha1))
+					}
+				})
+			).buffer_unordered(LARGE_FILE_PART_CONCURRENCY).collect::<Vec<Result<(u16, String)>>>().await;
+
+			for upload in uploads {
+				match upload {
+					Ok(part) => part_shas.push(part),
+					Err(e) => {
+						self.blaze.auth.cancel_large_file(&file_id).await?;
+						return Err(e);
+					}
+				}
+			}
+		}
+
+		part_shas.sort_by_key(|(part_number, _)| *part_number);
+		let part_sha1_array = part_shas.into_iter().map(|(_, sha1)| sha1).collect();
+
+		if let Err(e) = self.blaze.auth.finish_large_file(&file_id, part_sha1_array).await {
+			self.blaze.auth.cancel_large_file(&file_id).await?;
+			return Err(e);
 		}
 
 		Ok(())
 	}
 }
 
+async fn read_part(reader: &mut (impl tokio::io::AsyncRead + Unpin), part_size: usize) -> Result<Vec<u8>> {
+	let mut buf = vec![0u8; part_size];
+	let mut filled = 0;
+
+	while filled < part_size {
+		let n = reader.read(&mut buf[filled..]).await?;
+
+		if n == 0 {
+			break;
+		}
+
+		filled += n;
+	}
+
+	buf.truncate(filled);
+
+	Ok(buf)
+}
+
 
 async fn run_transfer(file_path: String, blaze: BlazeCache, url_cache: BlazeUploadUrlCache) -> Result<()> {
 	TransferToBlaze::new(file_path, blaze, url_cache)
@@ -163,8 +330,88 @@ async fn run_transfer(file_path: String, blaze: BlazeCache, url_cache: BlazeUplo
 }
 
 
+async fn retry_transfer_with_backoff(file_path: String, blaze: BlazeCache, url_cache: BlazeUploadUrlCache) -> Result<()> {
+	let mut attempt = 0;
+
+	loop {
+		match run_transfer(file_path.clone(), blaze.clone(), url_cache.clone()).await {
+			Ok(()) => return Ok(()),
+			Err(e) => {
+				attempt += 1;
+
+				if attempt >= MAX_RETRY_ATTEMPTS {
+					return Err(e);
+				}
+
+				let backoff = Duration::from_secs(2u64.pow(attempt));
+				eprintln!("Retry {}/{} for {:?} failed: {:?}. Backing off {:?}", attempt, MAX_RETRY_ATTEMPTS, file_path, e, backoff);
+				tokio::time::sleep(backoff).await;
+			}
+		}
+	}
+}
+
+/// Temp file + rename so a crash mid-write never leaves `.failed_transfers` half-written.
+async fn rewrite_failed_transfers(keys: &[String]) -> Result<()> {
+	let tmp_path = ".failed_transfers.tmp";
+
+	let mut tmp = File::create(tmp_path).await?;
+
+	for key in keys {
+		tmp.write_all(key.as_bytes()).await?;
+		tmp.write_all(b"\n").await?;
+	}
+
+	tmp.flush().await?;
+	tmp.sync_all().await?;
+
+	tokio::fs::rename(tmp_path, ".failed_transfers").await?;
+
+	Ok(())
+}
+
+async fn retry_failed_transfers(blaze: BlazeCache) -> Result<()> {
+	let contents = match tokio::fs::read_to_string(".failed_transfers").await {
+		Ok(contents) => contents,
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+		Err(e) => return Err(e.into())
+	};
+
+	let keys: Vec<String> = contents.lines().map(str::trim).filter(|l| !l.is_empty()).map(String::from).collect();
+
+	if keys.is_empty() {
+		println!("No failed transfers to retry");
+		return Ok(());
+	}
+
+	println!("Retrying {} failed transfer(s)", keys.len());
+
+	let url_cache = BlazeUploadUrlCache::new(&blaze).await?;
+
+	let mut still_failed = Vec::new();
+
+	for file_path in keys {
+		if let Err(e) = retry_transfer_with_backoff(file_path.clone(), blaze.clone(), url_cache.clone()).await {
+			eprintln!("Retry exhausted for {:?}: {:?}", file_path, e);
+			still_failed.push(file_path);
+		}
+	}
+
+	rewrite_failed_transfers(&still_failed).await?;
+
+	println!("Finished Retrying. {} still failing", still_failed.len());
+
+	Ok(())
+}
+
+
 #[tokio::main]
 async fn main() -> Result<()> {
+	if std::env::args().nth(1).as_deref() == Some("retry") {
+		let blaze = get_or_update_blaze_cache().await?;
+		return retry_failed_transfers(blaze).await;
+	}
+
 	println!("Starting");
 
 	// 1st step. Creates .aws_file